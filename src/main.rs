@@ -1,24 +1,196 @@
+use chrono::{DateTime, Utc};
 use clap::Parser;
 use jsonwebtoken::{encode, Algorithm, EncodingKey, Header};
 use reqwest::header::{ACCEPT, AUTHORIZATION, USER_AGENT};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fs;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::io::Write as _;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// How much time before actual expiry we treat the cached token as stale.
+const EXPIRY_SAFETY_MARGIN: Duration = Duration::from_secs(60);
+
+/// Config path used when `--config` isn't given and this file exists.
+const DEFAULT_CONFIG_PATH: &str = "create-gh-app-token.toml";
+
+/// Profile name used when `--profile` isn't given.
+const DEFAULT_PROFILE_NAME: &str = "default";
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
-    /// Path to GitHub App's private key PEM file
+    /// Path to GitHub App's private key PEM file. Ignored if `--key` (or
+    /// its `GH_APP_PRIVATE_KEY` env fallback) is set.
     #[arg(short, long)]
-    key_path: String,
+    key_path: Option<String>,
 
-    /// GitHub App ID
-    #[arg(short, long)]
-    app_id: String,
+    /// GitHub App's private key PEM contents, inline. Takes precedence
+    /// over `--key-path`. Falls back to the `GH_APP_PRIVATE_KEY` env var.
+    #[arg(long, env = "GH_APP_PRIVATE_KEY")]
+    key: Option<String>,
 
-    /// GitHub App Installation ID
-    #[arg(short, long)]
-    installation_id: String,
+    /// GitHub App ID. Falls back to the `GH_APP_ID` env var, then the
+    /// selected config profile.
+    #[arg(short, long, env = "GH_APP_ID")]
+    app_id: Option<String>,
+
+    /// GitHub App Installation ID. Falls back to the
+    /// `GH_APP_INSTALLATION_ID` env var, then the selected config profile.
+    #[arg(short, long, env = "GH_APP_INSTALLATION_ID")]
+    installation_id: Option<String>,
+
+    /// Restrict the token to this repository (owner/repo). Repeatable.
+    /// Falls back to the selected config profile's `repositories`.
+    #[arg(long = "repositories")]
+    repositories: Vec<String>,
+
+    /// Restrict the token to this permission, in `scope:level` form
+    /// (e.g. `contents:read`). Repeatable. Falls back to the selected
+    /// config profile's `permissions`.
+    #[arg(long = "permission", value_parser = parse_permission)]
+    permissions: Vec<(String, String)>,
+
+    /// Path to a TOML config file holding one or more named profiles.
+    /// Defaults to `create-gh-app-token.toml` in the current directory,
+    /// if present.
+    #[arg(long)]
+    config: Option<String>,
+
+    /// Name of the config profile to use. Defaults to `default`.
+    #[arg(long)]
+    profile: Option<String>,
+
+    /// Base URL of the GitHub API, e.g. `https://<host>/api/v3` for GitHub
+    /// Enterprise Server. Falls back to the `GITHUB_API_URL` env var.
+    #[arg(
+        long,
+        env = "GITHUB_API_URL",
+        default_value = "https://api.github.com"
+    )]
+    github_api_url: String,
+
+    /// How to print the minted token: `text` (default), `json`, or
+    /// `github-actions` (writes to `$GITHUB_OUTPUT` and masks the token in
+    /// logs via `::add-mask::`).
+    #[arg(long, value_enum, default_value = "text")]
+    output: OutputFormat,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum OutputFormat {
+    Text,
+    Json,
+    GithubActions,
+}
+
+/// A single named GitHub App profile loaded from a config file.
+#[derive(Debug, Clone, Default, Deserialize)]
+struct Profile {
+    app_id: Option<String>,
+    installation_id: Option<String>,
+    key_path: Option<String>,
+    #[serde(default)]
+    repositories: Vec<String>,
+    #[serde(default)]
+    permissions: HashMap<String, String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Config {
+    #[serde(default)]
+    profiles: HashMap<String, Profile>,
+}
+
+/// Loads the selected profile from `args.config` (or the default config
+/// path, if it exists), falling back to an empty profile when no config
+/// file is in play. CLI flags layered on top of the returned profile take
+/// precedence.
+fn load_profile(args: &Args) -> Result<Profile, Box<dyn std::error::Error>> {
+    let config_path = args
+        .config
+        .clone()
+        .unwrap_or_else(|| DEFAULT_CONFIG_PATH.to_string());
+
+    if !Path::new(&config_path).exists() {
+        if args.config.is_some() {
+            return Err(format!("config file not found: {config_path}").into());
+        }
+        return Ok(Profile::default());
+    }
+
+    let contents = fs::read_to_string(&config_path)?;
+    let config: Config = toml::from_str(&contents)?;
+    let profile_name = args.profile.as_deref().unwrap_or(DEFAULT_PROFILE_NAME);
+
+    match config.profiles.get(profile_name).cloned() {
+        Some(profile) => Ok(profile),
+        None if args.config.is_some() || args.profile.is_some() => Err(format!(
+            "no profile named `{profile_name}` in {config_path}"
+        )
+        .into()),
+        None => Ok(Profile::default()),
+    }
+}
+
+/// JSON shape emitted by `--output json`.
+#[derive(Debug, Serialize)]
+struct TokenOutput<'a> {
+    token: &'a str,
+    expires_at: &'a str,
+}
+
+/// Writes the token and expiry to the file named by the `GITHUB_OUTPUT`
+/// env var, in the `key=value` step-output format, and masks the token in
+/// the job log via an `::add-mask::` workflow command.
+fn write_github_actions_output(
+    token: &str,
+    expires_at: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    println!("::add-mask::{token}");
+
+    let output_path = std::env::var("GITHUB_OUTPUT")
+        .map_err(|_| "GITHUB_OUTPUT env var not set; are you running inside a GitHub Actions step?")?;
+    let mut file = fs::OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(output_path)?;
+    writeln!(file, "token={token}")?;
+    writeln!(file, "expires_at={expires_at}")?;
+
+    Ok(())
+}
+
+/// Validates that `url` is a well-formed absolute URL, returning it
+/// unchanged so it can be threaded straight into the token request.
+fn validate_github_api_url(url: String) -> Result<String, Box<dyn std::error::Error>> {
+    reqwest::Url::parse(&url).map_err(|e| format!("invalid --github-api-url `{url}`: {e}"))?;
+    Ok(url)
+}
+
+/// Parses a `scope:level` CLI argument into its two halves.
+fn parse_permission(s: &str) -> Result<(String, String), String> {
+    let (scope, level) = s
+        .split_once(':')
+        .ok_or_else(|| format!("invalid permission `{s}`, expected `scope:level`"))?;
+    Ok((scope.to_string(), level.to_string()))
+}
+
+/// Resolves the GitHub App private key PEM regardless of whether it came
+/// from `--key`, `GH_APP_PRIVATE_KEY`, a file at `--key-path`, or the
+/// selected config profile's `key_path`.
+fn resolve_private_key(
+    args: &Args,
+    profile: &Profile,
+) -> Result<String, Box<dyn std::error::Error>> {
+    if let Some(key) = &args.key {
+        return Ok(key.clone());
+    }
+    if let Some(key_path) = args.key_path.as_ref().or(profile.key_path.as_ref()) {
+        return Ok(fs::read_to_string(key_path)?);
+    }
+    Err("no private key provided: set --key, GH_APP_PRIVATE_KEY, --key-path, or a profile's key_path".into())
 }
 
 #[derive(Debug, Serialize)]
@@ -34,22 +206,161 @@ struct TokenResponse {
     expires_at: String,
 }
 
+/// Optional restrictions narrowing a minted token below the installation's
+/// full access. Sent as the request body only when something was asked for;
+/// GitHub treats an absent body as "grant everything the installation has".
+#[derive(Debug, Default, Serialize)]
+struct TokenRequest {
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    repositories: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    repository_ids: Vec<u64>,
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    permissions: HashMap<String, String>,
+}
+
+impl TokenRequest {
+    fn is_empty(&self) -> bool {
+        self.repositories.is_empty() && self.repository_ids.is_empty() && self.permissions.is_empty()
+    }
+}
+
+/// A cached installation access token that knows how to refresh itself.
+///
+/// Holds the private key and identifiers needed to re-mint the token so a
+/// long-running caller can keep one instance around and call `get` before
+/// every use instead of tracking expiry itself.
+struct InstallationAccessToken {
+    private_key: String,
+    app_id: String,
+    installation_id: String,
+    github_api_url: String,
+    scope: TokenRequest,
+    token: Option<String>,
+    expires_at: Option<SystemTime>,
+    expires_at_rfc3339: Option<String>,
+}
+
+impl InstallationAccessToken {
+    fn new(
+        private_key: String,
+        app_id: String,
+        installation_id: String,
+        github_api_url: String,
+        scope: TokenRequest,
+    ) -> Self {
+        Self {
+            private_key,
+            app_id,
+            installation_id,
+            github_api_url,
+            scope,
+            token: None,
+            expires_at: None,
+            expires_at_rfc3339: None,
+        }
+    }
+
+    /// Returns a valid installation token, refreshing it first if it's
+    /// missing or within `EXPIRY_SAFETY_MARGIN` of expiring.
+    async fn get(&mut self) -> Result<&str, Box<dyn std::error::Error>> {
+        let needs_refresh = match self.expires_at {
+            Some(expires_at) => {
+                SystemTime::now() + EXPIRY_SAFETY_MARGIN >= expires_at
+            }
+            None => true,
+        };
+
+        if needs_refresh {
+            let jwt = create_jwt(&self.private_key, &self.app_id)?;
+            let response = get_installation_token(
+                &jwt,
+                &self.installation_id,
+                &self.github_api_url,
+                &self.scope,
+            )
+            .await?;
+            let expires_at: DateTime<Utc> = response.expires_at.parse()?;
+            self.token = Some(response.token);
+            self.expires_at = Some(SystemTime::from(expires_at));
+            self.expires_at_rfc3339 = Some(response.expires_at);
+        }
+
+        Ok(self.token.as_deref().expect("token set above"))
+    }
+
+    /// The RFC 3339 expiry timestamp of the currently cached token, if one
+    /// has been minted yet.
+    fn expires_at(&self) -> Option<&str> {
+        self.expires_at_rfc3339.as_deref()
+    }
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let args = Args::parse();
-    
-    // Read private key from file
-    let private_key = fs::read_to_string(&args.key_path)?;
-    
-    // Create JWT token for GitHub API authentication
-    let jwt = create_jwt(&private_key, &args.app_id)?;
-    
-    // Exchange JWT for an installation token
-    let token = get_installation_token(&jwt, &args.installation_id).await?;
-    
-    println!("Installation Token: {}", token.token);
-    println!("Expires at: {}", token.expires_at);
-    
+    let profile = load_profile(&args)?;
+    let github_api_url = validate_github_api_url(args.github_api_url.clone())?;
+
+    let private_key = resolve_private_key(&args, &profile)?;
+
+    let app_id = args
+        .app_id
+        .clone()
+        .or(profile.app_id.clone())
+        .ok_or("missing --app-id (set via CLI, GH_APP_ID env var, or config profile)")?;
+    let installation_id = args
+        .installation_id
+        .clone()
+        .or(profile.installation_id.clone())
+        .ok_or("missing --installation-id (set via CLI, GH_APP_INSTALLATION_ID env var, or config profile)")?;
+
+    let repositories = if args.repositories.is_empty() {
+        profile.repositories
+    } else {
+        args.repositories
+    };
+    let permissions = if args.permissions.is_empty() {
+        profile.permissions
+    } else {
+        args.permissions.into_iter().collect()
+    };
+
+    let scope = TokenRequest {
+        repositories,
+        repository_ids: Vec::new(),
+        permissions,
+    };
+    let mut installation_token = InstallationAccessToken::new(
+        private_key,
+        app_id,
+        installation_id,
+        github_api_url,
+        scope,
+    );
+    let token = installation_token.get().await?.to_string();
+    let expires_at = installation_token
+        .expires_at()
+        .expect("expires_at set alongside token")
+        .to_string();
+
+    match args.output {
+        OutputFormat::Text => {
+            println!("Installation Token: {}", token);
+            println!("Expires at: {}", expires_at);
+        }
+        OutputFormat::Json => {
+            let output = TokenOutput {
+                token: &token,
+                expires_at: &expires_at,
+            };
+            println!("{}", serde_json::to_string(&output)?);
+        }
+        OutputFormat::GithubActions => {
+            write_github_actions_output(&token, &expires_at)?;
+        }
+    }
+
     Ok(())
 }
 
@@ -76,21 +387,31 @@ fn create_jwt(private_key: &str, app_id: &str) -> Result<String, Box<dyn std::er
     Ok(token)
 }
 
-async fn get_installation_token(jwt: &str, installation_id: &str) -> Result<TokenResponse, Box<dyn std::error::Error>> {
+async fn get_installation_token(
+    jwt: &str,
+    installation_id: &str,
+    github_api_url: &str,
+    scope: &TokenRequest,
+) -> Result<TokenResponse, Box<dyn std::error::Error>> {
     let client = reqwest::Client::new();
-    
+
     let url = format!(
-        "https://api.github.com/app/installations/{}/access_tokens", 
+        "{}/app/installations/{}/access_tokens",
+        github_api_url.trim_end_matches('/'),
         installation_id
     );
-    
-    let response = client
+
+    let mut request = client
         .post(&url)
         .header(USER_AGENT, "rust-github-app-token-generator")
         .header(ACCEPT, "application/vnd.github.v3+json")
-        .header(AUTHORIZATION, format!("Bearer {}", jwt))
-        .send()
-        .await?;
+        .header(AUTHORIZATION, format!("Bearer {}", jwt));
+
+    if !scope.is_empty() {
+        request = request.json(scope);
+    }
+
+    let response = request.send().await?;
     
     if !response.status().is_success() {
         let error_text = response.text().await?;